@@ -18,6 +18,8 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -28,11 +30,22 @@ use tracing::{debug, warn};
 const DEFAULT_MAX_RETRY_ATTEMPTS: usize = 30;
 const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(if cfg!(test) { 50 } else { 250 });
 const DEFAULT_MAX_DELAY: Duration = Duration::from_millis(if cfg!(test) { 1_000 } else { 20_000 });
+const DEFAULT_RETRY_QUOTA_CAPACITY: u32 = 500;
+const DEFAULT_RETRY_COST: u32 = 10;
+const RETRY_SUCCESS_CREDIT: u32 = 1;
 
 pub trait Retryable {
     fn is_retryable(&self) -> bool {
         false
     }
+
+    /// Number of tokens withdrawn from a shared [`RetryQuota`] (if any) when this error triggers
+    /// a retry. Defaults to [`DEFAULT_RETRY_COST`]; override to charge less for errors that are
+    /// cheap to retry (e.g. timeouts) so they don't drain the fleet-wide budget as fast as
+    /// errors that indicate the downstream is actually struggling.
+    fn retry_cost(&self) -> u32 {
+        DEFAULT_RETRY_COST
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -71,11 +84,222 @@ where E: Retryable
     }
 }
 
+/// Strategy used to spread out the delay between two retry attempts.
+///
+/// The three variants come from the AWS exponential-backoff-and-jitter analysis
+/// (<https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/>); each one trades
+/// off spread and guaranteed minimum wait differently, so which one wins depends on the
+/// contention profile of the downstream being called.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BackoffStrategy {
+    /// `delay = rand(0, min(max_delay, base_delay * 2^attempt))`. The current default: maximum
+    /// spread, but no guaranteed minimum wait.
+    FullJitter,
+    /// `temp = min(max_delay, base_delay * 2^attempt)`, `delay = temp/2 + rand(0, temp/2)`. Less
+    /// spread than full jitter, but never sleeps less than half of the ceiling.
+    EqualJitter,
+    /// `delay = min(max_delay, rand(base_delay, prev_delay * 3))`, ignoring the attempt counter
+    /// entirely. Spreads retries out over time better than the other two under sustained
+    /// contention, at the cost of depending on the previous delay.
+    Decorrelated,
+}
+
+impl Default for BackoffStrategy {
+    fn default() -> Self {
+        BackoffStrategy::FullJitter
+    }
+}
+
+/// The sequence of delays the retry loop sleeps between attempts, produced by a
+/// [`BackoffBuilder`]. The loop stops retrying once this iterator is exhausted, so a builder's
+/// length implicitly bounds the number of attempts.
+pub struct Backoff(Box<dyn Iterator<Item = Duration> + Send>);
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.0.next()
+    }
+}
+
+/// Builds the [`Backoff`] delay sequence driven by the retry loop. Implementing this directly
+/// (rather than hardcoding a schedule in the retry driver) lets different subsystems define and
+/// compose their own named backoff profiles.
+pub trait BackoffBuilder {
+    fn build(&self) -> Backoff;
+}
+
+/// Sleeps the same fixed `delay` between every attempt, for `max_attempts` attempts.
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantBackoff {
+    pub delay: Duration,
+    pub max_attempts: usize,
+}
+
+impl BackoffBuilder for ConstantBackoff {
+    fn build(&self) -> Backoff {
+        // The loop already performs one attempt before consuming any delay, so the iterator
+        // only needs to cover the sleeps between attempts, not the attempts themselves.
+        Backoff(Box::new(
+            std::iter::repeat(self.delay).take(self.max_attempts.saturating_sub(1)),
+        ))
+    }
+}
+
+/// Exponentially growing delay, capped at `max_delay` and spread out according to `strategy`.
+/// This is the backoff schedule [`retry`] and [`retry_if`] use by default.
+#[derive(Clone, Copy, Debug)]
+pub struct ExponentialBackoff {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub max_attempts: usize,
+    pub strategy: BackoffStrategy,
+}
+
+impl BackoffBuilder for ExponentialBackoff {
+    fn build(&self) -> Backoff {
+        let base_delay_ms = self.base_delay.as_millis() as u64;
+        let max_delay_ms = self.max_delay.as_millis() as u64;
+        let strategy = self.strategy;
+        // The loop already performs one attempt before consuming any delay, so the iterator
+        // only needs to cover the sleeps between attempts, not the attempts themselves.
+        let max_delays = self.max_attempts.saturating_sub(1);
+        let mut attempt: u32 = 0;
+        let mut prev_delay_ms = base_delay_ms;
+        Backoff(Box::new(std::iter::from_fn(move || {
+            if attempt as usize >= max_delays {
+                return None;
+            }
+            attempt += 1;
+            let delay_ms = match strategy {
+                BackoffStrategy::FullJitter => {
+                    // Saturate rather than overflow: a caller raising `max_attempts` well past
+                    // the default means `attempt` can grow large enough that `2^attempt` would
+                    // otherwise overflow the `u64` multiply and panic in debug builds.
+                    let ceiling_ms = base_delay_ms
+                        .saturating_mul(2u64.saturating_pow(attempt))
+                        .min(max_delay_ms);
+                    rand::thread_rng().gen_range(0..ceiling_ms.max(1))
+                }
+                BackoffStrategy::EqualJitter => {
+                    let ceiling_ms = base_delay_ms
+                        .saturating_mul(2u64.saturating_pow(attempt))
+                        .min(max_delay_ms);
+                    let half_ms = ceiling_ms / 2;
+                    half_ms + rand::thread_rng().gen_range(0..=half_ms)
+                }
+                BackoffStrategy::Decorrelated => {
+                    let ceiling_ms = prev_delay_ms.saturating_mul(3).max(base_delay_ms + 1);
+                    let delay_ms = rand::thread_rng()
+                        .gen_range(base_delay_ms..ceiling_ms)
+                        .min(max_delay_ms);
+                    prev_delay_ms = delay_ms;
+                    delay_ms
+                }
+            };
+            Some(Duration::from_millis(delay_ms))
+        })))
+    }
+}
+
+/// Delay grows along the Fibonacci sequence (scaled by `base_delay`) instead of doubling on
+/// every attempt, capped at `max_delay`. Grows more gently than [`ExponentialBackoff`], useful
+/// when a caller wants many attempts without the ceiling blowing up as fast.
+#[derive(Clone, Copy, Debug)]
+pub struct FibonacciBackoff {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub max_attempts: usize,
+}
+
+impl BackoffBuilder for FibonacciBackoff {
+    fn build(&self) -> Backoff {
+        let base_delay_ms = self.base_delay.as_millis() as u64;
+        let max_delay_ms = self.max_delay.as_millis() as u64;
+        // The loop already performs one attempt before consuming any delay, so the iterator
+        // only needs to cover the sleeps between attempts, not the attempts themselves.
+        let max_delays = self.max_attempts.saturating_sub(1);
+        let mut attempt = 0usize;
+        let (mut prev, mut curr) = (0u64, 1u64);
+        Backoff(Box::new(std::iter::from_fn(move || {
+            if attempt >= max_delays {
+                return None;
+            }
+            attempt += 1;
+            let delay_ms = base_delay_ms.saturating_mul(curr).min(max_delay_ms);
+            let next = prev + curr;
+            prev = curr;
+            curr = next;
+            Some(Duration::from_millis(delay_ms))
+        })))
+    }
+}
+
+/// A shared token bucket that bounds the total number of concurrent retries across every call
+/// using the same [`RetryParams`], so a partial outage does not turn into a retry storm that
+/// further overloads the struggling downstream service (e.g. ingester, metastore).
+///
+/// Modeled on the adaptive retry token bucket used by the AWS SDKs: each retry withdraws a cost
+/// (see [`Retryable::retry_cost`]) from the shared balance before sleeping, and an eventual
+/// success returns a small credit so a healthy steady state keeps the bucket topped up. The
+/// balance is shared via `Arc`, so cloning a `RetryQuota` shares the same bucket.
+#[derive(Clone)]
+pub struct RetryQuota {
+    balance: Arc<AtomicU32>,
+    capacity: u32,
+}
+
+impl RetryQuota {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            balance: Arc::new(AtomicU32::new(capacity)),
+            capacity,
+        }
+    }
+
+    /// Withdraws `cost` tokens from the shared balance. Returns `false` if the balance is too
+    /// low, in which case the caller should give up retrying immediately.
+    fn withdraw(&self, cost: u32) -> bool {
+        self.balance
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |balance| {
+                balance.checked_sub(cost)
+            })
+            .is_ok()
+    }
+
+    /// Returns `credit` tokens to the shared balance, capped at the bucket's capacity.
+    fn deposit(&self, credit: u32) {
+        let _ = self
+            .balance
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |balance| {
+                Some((balance + credit).min(self.capacity))
+            });
+    }
+}
+
+impl Default for RetryQuota {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETRY_QUOTA_CAPACITY)
+    }
+}
+
 #[derive(Clone)]
 pub struct RetryParams {
     pub base_delay: Duration,
     pub max_delay: Duration,
     pub max_attempts: usize,
+    pub backoff_strategy: BackoffStrategy,
+    /// Shared retry budget. When set, a retry is only attempted if the quota has enough tokens
+    /// left; otherwise the last error is returned as if it were non-retryable.
+    pub retry_quota: Option<RetryQuota>,
+    /// Caps the cumulative *backoff sleep* time, not wall-clock time. The budget only accounts
+    /// for the delays the loop sleeps between attempts; time spent awaiting `f()` itself isn't
+    /// counted, so a slow or hanging call can still run well past this duration before the next
+    /// attempt is even considered. Once the accumulated sleep time plus the next delay would
+    /// exceed this budget, the loop gives up and returns the last error, regardless of
+    /// `max_attempts`.
+    pub max_elapsed_time: Option<Duration>,
 }
 
 impl Default for RetryParams {
@@ -84,6 +308,21 @@ impl Default for RetryParams {
             base_delay: DEFAULT_BASE_DELAY,
             max_delay: DEFAULT_MAX_DELAY,
             max_attempts: DEFAULT_MAX_RETRY_ATTEMPTS,
+            backoff_strategy: BackoffStrategy::default(),
+            retry_quota: None,
+            max_elapsed_time: None,
+        }
+    }
+}
+
+impl RetryParams {
+    /// Builds the default [`ExponentialBackoff`] delay schedule from this `RetryParams`.
+    fn backoff_builder(&self) -> ExponentialBackoff {
+        ExponentialBackoff {
+            base_delay: self.base_delay,
+            max_delay: self.max_delay,
+            max_attempts: self.max_attempts,
+            strategy: self.backoff_strategy,
         }
     }
 }
@@ -93,16 +332,24 @@ trait MockableTime {
     async fn sleep(&self, duration: Duration);
 }
 
-async fn retry_with_mockable_time<U, E, Fut>(
+async fn retry_with_mockable_time<U, E, Fut, B>(
     retry_params: &RetryParams,
     f: impl Fn() -> Fut,
     mockable_time: impl MockableTime,
+    predicate: impl Fn(&E) -> bool,
+    retry_cost: impl Fn(&E) -> u32,
+    on_retry: impl Fn(&E, usize, Duration),
+    backoff_builder: B,
 ) -> Result<U, E>
 where
     Fut: Future<Output = Result<U, E>>,
-    E: Retryable + Debug + 'static,
+    E: Debug + 'static,
+    B: BackoffBuilder,
 {
+    let mut backoff = backoff_builder.build();
     let mut attempt_count = 0;
+    // Cumulative *sleep* time only, not wall-clock: see `RetryParams::max_elapsed_time`.
+    let mut elapsed = Duration::ZERO;
     loop {
         let response = f().await;
 
@@ -110,31 +357,52 @@ where
 
         match response {
             Ok(response) => {
+                if let Some(retry_quota) = &retry_params.retry_quota {
+                    retry_quota.deposit(RETRY_SUCCESS_CREDIT);
+                }
                 return Ok(response);
             }
             Err(error) => {
-                if !error.is_retryable() {
+                if !predicate(&error) {
                     return Err(error);
                 }
-                if attempt_count >= retry_params.max_attempts {
+                let Some(delay) = backoff.next() else {
                     warn!(
                         attempt_count = %attempt_count,
                         "Request failed"
                     );
                     return Err(error);
+                };
+                let elapsed_time_exhausted = retry_params
+                    .max_elapsed_time
+                    .is_some_and(|max_elapsed_time| elapsed + delay > max_elapsed_time);
+                if elapsed_time_exhausted {
+                    warn!(
+                        attempt_count = %attempt_count,
+                        "Request failed, max elapsed retry time exhausted"
+                    );
+                    return Err(error);
                 }
-
-                let ceiling_ms = (retry_params.base_delay.as_millis() as u64
-                    * 2u64.pow(attempt_count as u32))
-                .min(retry_params.max_delay.as_millis() as u64);
-                let delay_ms = rand::thread_rng().gen_range(0..ceiling_ms);
+                let quota_exhausted = retry_params
+                    .retry_quota
+                    .as_ref()
+                    .is_some_and(|retry_quota| !retry_quota.withdraw(retry_cost(&error)));
+                if quota_exhausted {
+                    warn!(
+                        attempt_count = %attempt_count,
+                        "Request failed, retry quota exhausted"
+                    );
+                    return Err(error);
+                }
+                on_retry(&error, attempt_count, delay);
                 debug!(
                     attempt_count = %attempt_count,
-                    delay_ms = %delay_ms,
+                    delay_ms = %delay.as_millis(),
                     error = ?error,
                     "Request failed, retrying"
                 );
-                mockable_time.sleep(Duration::from_millis(delay_ms)).await;
+                mockable_time.sleep(delay).await;
+                elapsed += delay;
             }
         }
     }
@@ -149,26 +417,108 @@ impl MockableTime for TokioTime {
     }
 }
 
-/// Retry with exponential backoff and full jitter. Implementation and default values originate from
+/// Retry with exponential backoff and jitter. Implementation and default values originate from
 /// the Java SDK. See also: <https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/>.
+/// The jitter spread is controlled by [`RetryParams::backoff_strategy`], which defaults to
+/// [`BackoffStrategy::FullJitter`].
 pub async fn retry<U, E, Fut>(retry_params: &RetryParams, f: impl Fn() -> Fut) -> Result<U, E>
 where
     Fut: Future<Output = Result<U, E>>,
     E: Retryable + Debug + 'static,
 {
-    retry_with_mockable_time(retry_params, f, TokioTime).await
+    retry_with_backoff(retry_params, f, retry_params.backoff_builder()).await
+}
+
+/// Like [`retry`], but drives the loop off an explicit [`BackoffBuilder`] instead of the
+/// [`ExponentialBackoff`] schedule derived from `retry_params`. Lets a caller plug in
+/// [`ConstantBackoff`] or [`FibonacciBackoff`] (or its own [`BackoffBuilder`] impl) when the
+/// default exponential schedule isn't the right fit.
+pub async fn retry_with_backoff<U, E, Fut, B>(
+    retry_params: &RetryParams,
+    f: impl Fn() -> Fut,
+    backoff_builder: B,
+) -> Result<U, E>
+where
+    Fut: Future<Output = Result<U, E>>,
+    E: Retryable + Debug + 'static,
+    B: BackoffBuilder,
+{
+    retry_with_mockable_time(
+        retry_params,
+        f,
+        TokioTime,
+        |error: &E| error.is_retryable(),
+        |error: &E| error.retry_cost(),
+        |_error: &E, _attempt_count: usize, _delay: Duration| {},
+        backoff_builder,
+    )
+    .await
+}
+
+/// Like [`retry`], but retryability is decided by `predicate` instead of the error's
+/// [`Retryable`] implementation. Useful for reusing the retry driver with error types that don't
+/// implement `Retryable`, or to retry the same error type differently depending on the caller
+/// (e.g. retry an HTTP error only on 5xx/429, not on 4xx). Since `E` isn't required to implement
+/// `Retryable` here, every retry withdraws the flat [`DEFAULT_RETRY_COST`] from the
+/// [`RetryParams::retry_quota`], if any.
+pub async fn retry_if<U, E, Fut>(
+    retry_params: &RetryParams,
+    f: impl Fn() -> Fut,
+    predicate: impl Fn(&E) -> bool,
+) -> Result<U, E>
+where
+    Fut: Future<Output = Result<U, E>>,
+    E: Debug + 'static,
+{
+    retry_with_mockable_time(
+        retry_params,
+        f,
+        TokioTime,
+        predicate,
+        |_error: &E| DEFAULT_RETRY_COST,
+        |_error: &E, _attempt_count: usize, _delay: Duration| {},
+        retry_params.backoff_builder(),
+    )
+    .await
+}
+
+/// Like [`retry`], but `on_retry` is invoked with the error, attempt count, and chosen delay
+/// right before each sleep, so callers can emit metrics or traces for retry rate and backoff
+/// without patching this crate.
+pub async fn retry_notify<U, E, Fut>(
+    retry_params: &RetryParams,
+    f: impl Fn() -> Fut,
+    on_retry: impl Fn(&E, usize, Duration),
+) -> Result<U, E>
+where
+    Fut: Future<Output = Result<U, E>>,
+    E: Retryable + Debug + 'static,
+{
+    retry_with_mockable_time(
+        retry_params,
+        f,
+        TokioTime,
+        |error: &E| error.is_retryable(),
+        |error: &E| error.retry_cost(),
+        on_retry,
+        retry_params.backoff_builder(),
+    )
+    .await
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::RwLock;
+    use std::sync::{Arc, Mutex, RwLock};
     use std::time::Duration;
 
     use async_trait::async_trait;
     use futures::future::ready;
     use quickwit_actors::{start_scheduler, SchedulerClient};
 
-    use super::{Retry, RetryParams};
+    use super::{
+        BackoffBuilder, BackoffStrategy, ConstantBackoff, ExponentialBackoff, FibonacciBackoff,
+        Retry, RetryParams, RetryQuota, Retryable, DEFAULT_RETRY_COST,
+    };
     use crate::retry::retry_with_mockable_time;
 
     #[async_trait]
@@ -178,18 +528,30 @@ mod tests {
         }
     }
 
-    async fn simulate_retries<T>(values: Vec<Result<T, Retry<usize>>>) -> Result<T, Retry<usize>> {
+    async fn simulate_retries_with_params<T>(
+        retry_params: RetryParams,
+        values: Vec<Result<T, Retry<usize>>>,
+    ) -> Result<T, Retry<usize>> {
         let scheduler_client = start_scheduler();
         scheduler_client.accelerate_time();
         let values_it = RwLock::new(values.into_iter());
+        let backoff_builder = retry_params.backoff_builder();
         retry_with_mockable_time(
-            &RetryParams::default(),
+            &retry_params,
             || ready(values_it.write().unwrap().next().unwrap()),
             scheduler_client,
+            |error: &Retry<usize>| error.is_retryable(),
+            |error: &Retry<usize>| error.retry_cost(),
+            |_error: &Retry<usize>, _attempt_count: usize, _delay: Duration| {},
+            backoff_builder,
         )
         .await
     }
 
+    async fn simulate_retries<T>(values: Vec<Result<T, Retry<usize>>>) -> Result<T, Retry<usize>> {
+        simulate_retries_with_params(RetryParams::default(), values).await
+    }
+
     #[tokio::test]
     async fn test_retry_accepts_ok() {
         assert_eq!(simulate_retries(vec![Ok(())]).await, Ok(()));
@@ -231,4 +593,174 @@ mod tests {
             .collect();
         assert_eq!(simulate_retries(retry_sequence).await, Ok(()));
     }
+
+    #[test]
+    fn test_exponential_backoff_equal_jitter_stays_within_bounds() {
+        let backoff_builder = ExponentialBackoff {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(10_000),
+            max_attempts: 5,
+            strategy: BackoffStrategy::EqualJitter,
+        };
+        let delays: Vec<_> = backoff_builder.build().collect();
+        assert_eq!(delays.len(), 4);
+        for (attempt, delay) in delays.into_iter().enumerate() {
+            let ceiling_ms = 100 * 2u64.pow(attempt as u32 + 1);
+            assert!(delay >= Duration::from_millis(ceiling_ms / 2));
+            assert!(delay <= Duration::from_millis(ceiling_ms));
+        }
+    }
+
+    #[test]
+    fn test_exponential_backoff_decorrelated_stays_within_bounds() {
+        let backoff_builder = ExponentialBackoff {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(10_000),
+            max_attempts: 5,
+            strategy: BackoffStrategy::Decorrelated,
+        };
+        let delays: Vec<_> = backoff_builder.build().collect();
+        assert_eq!(delays.len(), 4);
+        for delay in delays {
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_millis(10_000));
+        }
+    }
+
+    #[test]
+    fn test_retry_quota_withdraw_and_deposit() {
+        let retry_quota = RetryQuota::new(10);
+        assert!(retry_quota.withdraw(5));
+        assert!(!retry_quota.withdraw(6));
+        assert!(retry_quota.withdraw(5));
+        assert!(!retry_quota.withdraw(1));
+        // Deposits are capped at capacity, not left to grow unbounded.
+        retry_quota.deposit(100);
+        assert!(retry_quota.withdraw(10));
+        assert!(!retry_quota.withdraw(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_when_quota_exhausted() {
+        let retry_params = RetryParams {
+            retry_quota: Some(RetryQuota::new(10)),
+            ..RetryParams::default()
+        };
+        let retry_sequence = vec![Err(Retry::Transient(1)), Err(Retry::Transient(2)), Ok(())];
+        assert_eq!(
+            simulate_retries_with_params(retry_params, retry_sequence).await,
+            Err(Retry::Transient(2))
+        );
+    }
+
+    #[test]
+    fn test_constant_backoff_yields_max_attempts_minus_one_delays() {
+        let backoff_builder = ConstantBackoff {
+            delay: Duration::from_millis(50),
+            max_attempts: 4,
+        };
+        let delays: Vec<_> = backoff_builder.build().collect();
+        assert_eq!(delays, vec![Duration::from_millis(50); 3]);
+    }
+
+    #[test]
+    fn test_fibonacci_backoff_yields_max_attempts_minus_one_delays() {
+        let backoff_builder = FibonacciBackoff {
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10_000),
+            max_attempts: 6,
+        };
+        let delays: Vec<_> = backoff_builder.build().collect();
+        let expected_ms = [10, 10, 20, 30, 50];
+        assert_eq!(
+            delays,
+            expected_ms.into_iter().map(Duration::from_millis).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_if_uses_custom_predicate_instead_of_retryable() {
+        let scheduler_client = start_scheduler();
+        scheduler_client.accelerate_time();
+        let values = vec![Err(1), Err(2), Ok(())];
+        let values_it = RwLock::new(values.into_iter());
+        let retry_params = RetryParams::default();
+        let backoff_builder = retry_params.backoff_builder();
+        // Mirrors what `retry_if` does internally: retry only on errors the caller's predicate
+        // accepts, here even numbers, instead of relying on a `Retryable` impl.
+        let result = retry_with_mockable_time(
+            &retry_params,
+            || ready(values_it.write().unwrap().next().unwrap()),
+            scheduler_client,
+            |error: &i32| error % 2 == 0,
+            |_error: &i32| DEFAULT_RETRY_COST,
+            |_error: &i32, _attempt_count: usize, _delay: Duration| {},
+            backoff_builder,
+        )
+        .await;
+        assert_eq!(result, Err(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_aborts_when_max_elapsed_time_exceeded() {
+        let scheduler_client = start_scheduler();
+        scheduler_client.accelerate_time();
+        let values = vec![
+            Err(Retry::Transient(1)),
+            Err(Retry::Transient(2)),
+            Err(Retry::Transient(3)),
+            Ok(()),
+        ];
+        let values_it = RwLock::new(values.into_iter());
+        let retry_params = RetryParams {
+            max_elapsed_time: Some(Duration::from_millis(150)),
+            ..RetryParams::default()
+        };
+        // A constant 100ms schedule makes the elapsed-time bookkeeping deterministic: the first
+        // retry sleeps 100ms (elapsed 0 + 100 <= 150), the second would push elapsed to 200ms,
+        // which blows the 150ms budget before the third attempt is even made.
+        let result = retry_with_mockable_time(
+            &retry_params,
+            || ready(values_it.write().unwrap().next().unwrap()),
+            scheduler_client,
+            |error: &Retry<usize>| error.is_retryable(),
+            |error: &Retry<usize>| error.retry_cost(),
+            |_error: &Retry<usize>, _attempt_count: usize, _delay: Duration| {},
+            ConstantBackoff {
+                delay: Duration::from_millis(100),
+                max_attempts: 10,
+            },
+        )
+        .await;
+        assert_eq!(result, Err(Retry::Transient(2)));
+    }
+
+    #[tokio::test]
+    async fn test_retry_invokes_on_retry_before_each_sleep() {
+        let scheduler_client = start_scheduler();
+        scheduler_client.accelerate_time();
+        let values = vec![Err(Retry::Transient(1)), Err(Retry::Transient(2)), Ok(())];
+        let values_it = RwLock::new(values.into_iter());
+        let retry_params = RetryParams::default();
+        let backoff_builder = retry_params.backoff_builder();
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let notifications_clone = notifications.clone();
+        let result = retry_with_mockable_time(
+            &retry_params,
+            || ready(values_it.write().unwrap().next().unwrap()),
+            scheduler_client,
+            |error: &Retry<usize>| error.is_retryable(),
+            |error: &Retry<usize>| error.retry_cost(),
+            move |error: &Retry<usize>, attempt_count: usize, _delay: Duration| {
+                let inner = *match error {
+                    Retry::Transient(inner) | Retry::Permanent(inner) => inner,
+                };
+                notifications_clone.lock().unwrap().push((inner, attempt_count));
+            },
+            backoff_builder,
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(*notifications.lock().unwrap(), vec![(1, 1), (2, 2)]);
+    }
 }